@@ -1,20 +1,51 @@
 
-use std::{error::Error, str::from_utf8};
+use std::{collections::VecDeque, error::Error, io::{Read, Write}, ops::{Deref, DerefMut}, str::from_utf8, sync::{Arc, Mutex}};
 
 use base64::{Engine as _, engine::general_purpose};
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
 use serde::Deserialize;
 use serde_json::{Value, from_value};
 use simplehttp::simplehttp::{SimpleHttpClient, SimpleHttpError};
 use thiserror::Error;
 
+/// Below this size, `query` bodies are sent uncompressed even when
+/// compression is enabled; gzip's overhead isn't worth it for small queries.
+const GZIP_MIN_BODY_BYTES: usize = 8 * 1024;
+
 pub struct SurrealDbClient {
     base_url: String,
     namespace: String,
     database: String,
-    auth_token: String,
+    auth: Auth,
+    compression: bool,
     client: Box<dyn SimpleHttpClient>,
 }
 
+/// The credentials attached to outgoing requests as an `Authorization` header.
+///
+/// `Basic` is produced directly from a username/password pair (root or
+/// namespace/database users). `Bearer` holds a JWT obtained from `/signin` or
+/// `/signup`, which is how record-scope (`sc`) users authenticate.
+#[derive(Debug, Clone)]
+pub enum Auth {
+    Basic { token: String },
+    Bearer { token: String },
+}
+
+impl Auth {
+    fn header_value(&self) -> String {
+        match self {
+            Auth::Basic { token } => format!("Basic {token}"),
+            Auth::Bearer { token } => format!("Bearer {token}"),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct SignInResponse {
+    token: String,
+}
+
 #[derive(Debug,Error)]
 pub enum SurrealDbError {
     #[error("No result found")]
@@ -25,6 +56,12 @@ pub enum SurrealDbError {
     EmptyResult,
     #[error("Server error")]
     ServerError(String, SimpleHttpError),
+    #[error("Missing required field: {0}")]
+    MissingField(&'static str),
+    #[error("Transaction reply count did not match the number of statements added")]
+    TransactionReplyMismatch,
+    #[error("Credentials must be a JSON object")]
+    InvalidCredentials,
     #[error("Other")]
     Other(String,Box<dyn Error>),
 }
@@ -40,7 +77,15 @@ pub struct DynamicSurrealResult (Vec<DynamicSurrealStatementReply>);
 impl DynamicSurrealResult {
     pub fn take_first(mut self)->Result<DynamicSurrealStatementReply,SurrealDbError> {
         self.0.pop().ok_or(SurrealDbError::NoResult)
-    }    
+    }
+
+    /// Drop the leading `param_count` replies, i.e. the ones produced by the
+    /// `LET $name = ...;` statements `query_with_params` prepends, leaving
+    /// only the reply/replies for the statement(s) the caller wrote.
+    pub fn skip_params(mut self, param_count: usize)->Self {
+        self.0.drain(0..param_count.min(self.0.len()));
+        self
+    }
 }
 #[derive(Deserialize,Debug)]
 pub struct DynamicSurrealStatementReply {
@@ -69,61 +114,175 @@ pub struct SurrealStatementReply<T> {
     pub result: Vec<T>,
 }
 
+/// A graph record link that may or may not have been resolved by `FETCH`.
+/// Without `FETCH` (or for fields `FETCH` didn't target), SurrealDB returns
+/// the link as a bare record id string (`"actor:1"`), deserialized as
+/// `Link::Id`. With `FETCH`, the field holds the fully-expanded related
+/// record instead, deserialized as `Link::Record`.
+#[derive(Debug)]
+pub enum Link<T> {
+    Id(String),
+    Record(T),
+}
+
+impl<'de, T> Deserialize<'de> for Link<T> where T: Deserialize<'de> {
+    fn deserialize<D>(deserializer: D)->Result<Self, D::Error> where D: serde::Deserializer<'de> {
+        match Value::deserialize(deserializer)? {
+            Value::String(id) => Ok(Link::Id(id)),
+            record => from_value(record)
+                .map(Link::Record)
+                .map_err(serde::de::Error::custom),
+        }
+    }
+}
+
 
 impl SurrealDbClient {
-    // Add builder pattern?
     pub fn new(username: &str, password: &str, base_url: &str, namespace: &str, database: &str, client: Box<dyn SimpleHttpClient>)->Self {
-        let mut auth_token = String::new();
-        general_purpose::STANDARD.encode_string(format!("{}:{}",username,password), &mut auth_token);
-        Self { auth_token, base_url: base_url.to_owned(), namespace: namespace.to_owned(), database: database.to_owned(), client}
+        let mut token = String::new();
+        general_purpose::STANDARD.encode_string(format!("{}:{}",username,password), &mut token);
+        Self { auth: Auth::Basic { token }, base_url: base_url.to_owned(), namespace: namespace.to_owned(), database: database.to_owned(), compression: false, client}
     }
 
-    pub fn get(&mut self, table: &str, key: &str)->Result<Vec<u8>, SurrealDbError> {
+    /// Start building a client fluently, e.g. when the credentials are a
+    /// pre-obtained token rather than a username/password pair.
+    pub fn builder()->SurrealDbClientBuilder {
+        SurrealDbClientBuilder::default()
+    }
+
+    /// Opt into gzip compression: `Accept-Encoding: gzip` is sent on every
+    /// `get`/`delete`/`insert`/`query` request, their response bodies are
+    /// transparently inflated before parsing, and `query` bodies at or above
+    /// [`GZIP_MIN_BODY_BYTES`] are sent `Content-Encoding: gzip`. The
+    /// `is_healthy` check and the `sign_in`/`sign_up` exchange are always
+    /// sent without `Accept-Encoding`. Off by default.
+    pub fn set_compression(&mut self, enabled: bool) {
+        self.compression = enabled;
+    }
+
+    /// Start a transaction. Statements are accumulated with
+    /// [`SurrealDbTransaction::statement`] and sent together, wrapped in
+    /// `BEGIN TRANSACTION;`/`COMMIT TRANSACTION;`, when the transaction is
+    /// [`SurrealDbTransaction::commit`]ted.
+    pub fn transaction(&mut self)->SurrealDbTransaction {
+        SurrealDbTransaction { client: self, statements: Vec::new() }
+    }
+
+    /// Replace the credentials used for subsequent requests, e.g. with a
+    /// refreshed JWT. [`SurrealDbClient::sign_in`]/[`SurrealDbClient::sign_up`]
+    /// already switch the client to their `Bearer` token themselves.
+    pub fn set_auth(&mut self, auth: Auth) {
+        self.auth = auth;
+    }
+
+    /// Exchange credentials for a JWT via SurrealDB's `/signin` endpoint and
+    /// switch this client's auth to `Bearer`. `scope` selects a record-scope
+    /// (`sc`) user; pass `None` to sign in as a root/namespace/database user.
+    /// `credentials` must be a JSON object holding the scope's expected
+    /// fields (e.g. `{"user": "...", "pass": "..."}`); `ns`/`db`/`sc` are
+    /// added automatically.
+    pub fn sign_in(&mut self, scope: Option<&str>, credentials: &Value)->Result<(), SurrealDbError> {
+        let token = self.auth_exchange("/signin", scope, credentials)?;
+        self.auth = Auth::Bearer { token };
+        Ok(())
+    }
+
+    /// Register a new record-scope user via SurrealDB's `/signup` endpoint
+    /// and switch this client's auth to the returned `Bearer` token.
+    /// `credentials` must be a JSON object holding the scope's signup
+    /// fields; `ns`/`db`/`sc` are added automatically.
+    pub fn sign_up(&mut self, scope: &str, credentials: &Value)->Result<(), SurrealDbError> {
+        let token = self.auth_exchange("/signup", Some(scope), credentials)?;
+        self.auth = Auth::Bearer { token };
+        Ok(())
+    }
+
+    fn auth_exchange(&mut self, path: &str, scope: Option<&str>, credentials: &Value)->Result<String, SurrealDbError> {
+        let mut body = credentials.clone();
+        let map = body.as_object_mut().ok_or(SurrealDbError::InvalidCredentials)?;
+        map.insert("ns".to_owned(), Value::String(self.namespace.clone()));
+        map.insert("db".to_owned(), Value::String(self.database.clone()));
+        if let Some(scope) = scope {
+            map.insert("sc".to_owned(), Value::String(scope.to_owned()));
+        }
+        let payload = serde_json::to_vec(&body)
+            .map_err(|e| SurrealDbError::Other(format!("Error serializing credentials for: {}",path),Box::new(e)))?;
         let headers = [
+            ("Accept","application/json"),
+            ("Content-Type","application/json"),
+        ];
+        let url = format!("{}{}",self.base_url,path);
+        let result = self.client.post(&url, &headers, &payload)
+            .map_err(|e| SurrealDbError::ServerError(format!("Error calling: {}",path),e))?;
+        let response: SignInResponse = serde_json::from_slice(&result)
+            .map_err(|e| SurrealDbError::Other(format!("Error parsing json result from: {}",path),Box::new(e)))?;
+        Ok(response.token)
+    }
+
+    /// The headers common to every authenticated request, plus
+    /// `Accept-Encoding: gzip` when compression is enabled.
+    fn headers<'a>(&'a self, auth_header: &'a str)->Vec<(&'a str,&'a str)> {
+        let mut headers = vec![
             ("DB",self.database.as_str()),
             ("NS",self.namespace.as_str()),
             ("Accept","application/json"),
-            ("Authorization",&format!("Basic {}",self.auth_token))
+            ("Authorization",auth_header),
         ];
+        if self.compression {
+            headers.push(("Accept-Encoding","gzip"));
+        }
+        headers
+    }
+
+    /// Inflate `bytes` if it looks like a gzip stream (magic number `1f 8b`);
+    /// otherwise return it unchanged. `SimpleHttpClient` doesn't expose
+    /// response headers, so detection goes by content rather than by
+    /// checking for a `Content-Encoding: gzip` response header.
+    fn maybe_decompress(bytes: Vec<u8>)->Result<Vec<u8>, SurrealDbError> {
+        if !bytes.starts_with(&[0x1f, 0x8b]) {
+            return Ok(bytes);
+        }
+        let mut decompressed = Vec::new();
+        GzDecoder::new(&bytes[..]).read_to_end(&mut decompressed)
+            .map_err(|e| SurrealDbError::Other("Error decompressing gzip response".to_owned(),Box::new(e)))?;
+        Ok(decompressed)
+    }
+
+    pub fn get(&mut self, table: &str, key: &str)->Result<Vec<u8>, SurrealDbError> {
+        let auth_header = self.auth.header_value();
+        let headers = self.headers(&auth_header);
         let url = format!("{}/key/{}/{}",self.base_url,table,key);
         let result = self.client.get(&url, &headers[..])
             .map_err(|e| SurrealDbError::ServerError(format!("Error getting table: {} id: {}",table,key),e))?;
-        Ok(result)
+        Self::maybe_decompress(result)
     }
 
     /// Delete the supplied key from the table. **If no key is supplied, the whole table is deleted**
     pub fn delete(&mut self, table: &str, key: Option<&str>)->Result<Vec<u8>, SurrealDbError> {
-        let headers = [
-            ("DB",self.database.as_str()),
-            ("NS",self.namespace.as_str()),
-            ("Accept","application/json"),
-            ("Authorization",&format!("Basic {}",self.auth_token))
-        ];
+        let auth_header = self.auth.header_value();
+        let headers = self.headers(&auth_header);
 
         let url = match key {
             Some(key)=>format!("{}/key/{}/{}",self.base_url,table,key),
             None => format!("{}/key/{}",self.base_url,table),
         };
-    
+
         let result = self.client.delete(&url, &headers[..])
             .map_err(|e| SurrealDbError::ServerError(format!("Error getting table: {} id: {:?}",table,key),e))?;
-        Ok(result)
+        Self::maybe_decompress(result)
     }
 
     // DynamicSurrealResult
     fn insert(&mut self, table: &str, key: Option<&str>, value: &[u8])->Result<DynamicSurrealResult, SurrealDbError> {
-        let headers = [
-            ("DB",self.database.as_str()),
-            ("NS",self.namespace.as_str()),
-            ("Accept","application/json"),
-            ("Authorization",&format!("Basic {}",self.auth_token))
-        ];
+        let auth_header = self.auth.header_value();
+        let headers = self.headers(&auth_header);
         let url = match key {
             Some(key) => format!("{}/key/{}/{}",self.base_url,table,key),
             None => format!("{}/key/{}",self.base_url,table),
         };
         let inserted = self.client.post(&url, &headers,value)
             .map_err(|e| SurrealDbError::ServerError(format!("Error querying table: {} key: {:?}",table,key),e))?;
+        let inserted = Self::maybe_decompress(inserted)?;
         let parsed: Value = serde_json::from_slice(&inserted)
             .map_err(|e| SurrealDbError::Other(format!("Error parsing json result from insert at table: {} key: {:?}",table, key),Box::new(e)))?;
         let l = from_value::<DynamicSurrealResult>(parsed)
@@ -155,16 +314,83 @@ impl SurrealDbClient {
     }
 
     pub fn query(&mut self, query: &str)->Result<Vec<u8>, SurrealDbError> {
-        let headers = [
-            ("DB",self.database.as_str()),
-            ("NS",self.namespace.as_str()),
-            ("Accept","application/json"),
-            ("Authorization",&format!("Basic {}",self.auth_token))
-        ];
+        let auth_header = self.auth.header_value();
+        let mut headers = self.headers(&auth_header);
+
+        let compress_body = self.compression && query.len() >= GZIP_MIN_BODY_BYTES;
+        let body = if compress_body {
+            headers.push(("Content-Encoding","gzip"));
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(query.as_bytes())
+                .and_then(|_| encoder.finish())
+                .map_err(|e| SurrealDbError::Other(format!("Error compressing query: {}",query),Box::new(e)))?
+        } else {
+            query.as_bytes().to_vec()
+        };
+
         let url = format!("{}/sql",self.base_url);
-        let result = self.client.post(&url, &headers[..], query.as_bytes())
+        let result = self.client.post(&url, &headers[..], &body)
             .map_err(|e| SurrealDbError::ServerError(format!("Error querying: {}",query),e))?;
-        Ok(result)
+        Self::maybe_decompress(result)
+    }
+
+    /// Build a safe parameterized query body: each `(name, value)` pair is
+    /// emitted as a `LET $name = <json>;` statement ahead of `query`, so the
+    /// value is always sent as a JSON literal and never needs caller-side
+    /// quoting or escaping. `$name` placeholders used in `query` must match
+    /// the names supplied here.
+    pub fn query_with_params(&mut self, query: &str, params: &[(&str, &Value)])->Result<Vec<u8>, SurrealDbError> {
+        let full_query = Self::bind_params(query, params);
+        self.query(&full_query)
+    }
+
+    /// Like [`SurrealDbClient::query_dynamic_single`], but with `params`
+    /// bound the same way as [`SurrealDbClient::query_with_params`]. The
+    /// replies produced by the `LET` statements are dropped, leaving only
+    /// the reply/replies for `query` itself.
+    pub fn query_dynamic_with_params(&mut self, query: &str, params: &[(&str, &Value)])->Result<DynamicSurrealResult,SurrealDbError> {
+        let full_query = Self::bind_params(query, params);
+        let result = self.query_dynamic_single(&full_query)?;
+        Ok(result.skip_params(params.len()))
+    }
+
+    /// Like [`SurrealDbClient::query_single`], but with `params` bound the
+    /// same way as [`SurrealDbClient::query_with_params`]. Only the last
+    /// statement's reply, which is always `query`'s own reply since the
+    /// `LET` statements are prepended, is deserialized into `T`; the `LET`
+    /// replies (whose `result` may be null) are skipped unparsed.
+    pub fn query_single_with_params<T>(&mut self, query: &str, params: &[(&str, &Value)])->Result<SurrealStatementReply<T>,SurrealDbError> where T: for<'a> Deserialize<'a> {
+        let full_query = Self::bind_params(query, params);
+        let value = self.query(&full_query)?;
+        let replies: Vec<Value> = serde_json::from_slice(&value)
+            .map_err(|e| SurrealDbError::Other(format!("Error parsing json result: {}",full_query),Box::new(e)))?;
+        let last = replies.into_iter().last().ok_or(SurrealDbError::EmptyResult)?;
+        from_value::<SurrealStatementReply<T>>(last)
+            .map_err(|e| SurrealDbError::Other(format!("Error parsing json result: {}",full_query),Box::new(e)))
+    }
+
+    fn bind_params(query: &str, params: &[(&str, &Value)])->String {
+        let mut full_query = String::new();
+        for (name, value) in params {
+            full_query.push_str(&format!("LET ${name} = {};\n", serde_json::to_string(value).unwrap()));
+        }
+        full_query.push_str(query);
+        full_query
+    }
+
+    /// Like [`SurrealDbClient::query_single`], but appends a `FETCH
+    /// <fields>` clause so graph traversal results expand their record
+    /// links into full objects instead of bare ids. Deserialize link fields
+    /// as [`Link<T>`] to accept either form depending on whether the field
+    /// was named in `fetch`.
+    pub fn query_fetch<T>(&mut self, query: &str, fetch: &[&str])->Result<SurrealStatementReply<T>,SurrealDbError> where T: for<'a> Deserialize<'a> {
+        let full_query = Self::append_fetch(query, fetch);
+        self.query_single(&full_query)
+    }
+
+    fn append_fetch(query: &str, fetch: &[&str])->String {
+        let trimmed = query.trim_end().strip_suffix(';').unwrap_or(query.trim_end());
+        format!("{} FETCH {};", trimmed, fetch.join(", "))
     }
 
     pub fn query_dynamic_single(&mut self, query: &str)->Result<DynamicSurrealResult,SurrealDbError> {
@@ -186,16 +412,404 @@ impl SurrealDbClient {
     }
 }
 
+enum Credentials {
+    UsernamePassword(String, String),
+    Token(Auth),
+}
+
+/// Fluent builder for [`SurrealDbClient`]. `base_url`, `namespace`,
+/// `database`, `client` and either `username_password` or `token` are all
+/// required; `build` reports the first one missing.
+#[derive(Default)]
+pub struct SurrealDbClientBuilder {
+    base_url: Option<String>,
+    namespace: Option<String>,
+    database: Option<String>,
+    client: Option<Box<dyn SimpleHttpClient>>,
+    credentials: Option<Credentials>,
+    compression: bool,
+}
+
+impl SurrealDbClientBuilder {
+    /// Opt into gzip compression, see [`SurrealDbClient::set_compression`]. Off by default.
+    pub fn compression(mut self, enabled: bool)->Self {
+        self.compression = enabled;
+        self
+    }
+
+    pub fn base_url(mut self, base_url: &str)->Self {
+        self.base_url = Some(base_url.to_owned());
+        self
+    }
+
+    pub fn namespace(mut self, namespace: &str)->Self {
+        self.namespace = Some(namespace.to_owned());
+        self
+    }
+
+    pub fn database(mut self, database: &str)->Self {
+        self.database = Some(database.to_owned());
+        self
+    }
+
+    pub fn client(mut self, client: Box<dyn SimpleHttpClient>)->Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Authenticate with a username/password pair, Basic-encoded the same
+    /// way [`SurrealDbClient::new`] does.
+    pub fn username_password(mut self, username: &str, password: &str)->Self {
+        self.credentials = Some(Credentials::UsernamePassword(username.to_owned(), password.to_owned()));
+        self
+    }
+
+    /// Authenticate with a pre-obtained token, e.g. a JWT obtained
+    /// out-of-band from SurrealDB's `/signin` or `/signup` endpoint.
+    pub fn token(mut self, auth: Auth)->Self {
+        self.credentials = Some(Credentials::Token(auth));
+        self
+    }
+
+    pub fn build(self)->Result<SurrealDbClient, SurrealDbError> {
+        let base_url = self.base_url.ok_or(SurrealDbError::MissingField("base_url"))?;
+        let namespace = self.namespace.ok_or(SurrealDbError::MissingField("namespace"))?;
+        let database = self.database.ok_or(SurrealDbError::MissingField("database"))?;
+        let client = self.client.ok_or(SurrealDbError::MissingField("client"))?;
+        let auth = match self.credentials.ok_or(SurrealDbError::MissingField("username_password or token"))? {
+            Credentials::Token(auth) => auth,
+            Credentials::UsernamePassword(username, password) => {
+                let mut token = String::new();
+                general_purpose::STANDARD.encode_string(format!("{}:{}",username,password), &mut token);
+                Auth::Basic { token }
+            }
+        };
+        Ok(SurrealDbClient { base_url, namespace, database, auth, compression: self.compression, client })
+    }
+}
+
+/// A transaction being assembled via [`SurrealDbClient::transaction`].
+/// Statements are sent together wrapped in `BEGIN TRANSACTION;`/`COMMIT
+/// TRANSACTION;` once [`SurrealDbTransaction::commit`] is called.
+pub struct SurrealDbTransaction<'a> {
+    client: &'a mut SurrealDbClient,
+    statements: Vec<String>,
+}
+
+impl<'a> SurrealDbTransaction<'a> {
+    pub fn statement(mut self, statement: &str)->Self {
+        self.statements.push(statement.to_owned());
+        self
+    }
+
+    /// Send the accumulated statements as a single transaction and return
+    /// one reply per statement added, in order. If any statement's reply
+    /// has `status: ERR`, the whole transaction rolled back server-side and
+    /// this returns `Err(SurrealDbError::NotOkStatus(SurrealStatus::ERR))`
+    /// rather than a partial result. If the server didn't return exactly one
+    /// reply per statement plus the BEGIN/COMMIT TRANSACTION control
+    /// replies, this returns `Err(SurrealDbError::TransactionReplyMismatch)`
+    /// rather than silently handing back misaligned results.
+    pub fn commit(self)->Result<Vec<DynamicSurrealStatementReply>, SurrealDbError> {
+        let statement_count = self.statements.len();
+        let mut body = String::from("BEGIN TRANSACTION;\n");
+        for statement in &self.statements {
+            body.push_str(statement);
+            if !statement.trim_end().ends_with(';') {
+                body.push(';');
+            }
+            body.push('\n');
+        }
+        body.push_str("COMMIT TRANSACTION;");
+
+        let result = self.client.query_dynamic_single(&body)?;
+        align_transaction_replies(result.0, statement_count)
+    }
+}
+
+/// Drop the BEGIN/COMMIT TRANSACTION control replies from `replies` so the
+/// result aligns with the `statement_count` statements the caller added,
+/// then check each remaining reply for `status: ERR`.
+///
+/// Returns `Err(SurrealDbError::TransactionReplyMismatch)` if `replies`
+/// isn't exactly `statement_count + 2` long (one reply per statement plus
+/// BEGIN/COMMIT), since there would be no correct way to align it. Returns
+/// `Err(SurrealDbError::NotOkStatus(SurrealStatus::ERR))` if any aligned
+/// reply's status is `ERR` (the whole transaction rolled back server-side).
+fn align_transaction_replies(mut replies: Vec<DynamicSurrealStatementReply>, statement_count: usize)->Result<Vec<DynamicSurrealStatementReply>, SurrealDbError> {
+    if replies.len() != statement_count + 2 {
+        return Err(SurrealDbError::TransactionReplyMismatch);
+    }
+    replies.remove(0);
+    replies.pop();
+    if replies.iter().any(|reply| reply.status == SurrealStatus::ERR) {
+        return Err(SurrealDbError::NotOkStatus(SurrealStatus::ERR));
+    }
+    Ok(replies)
+}
+
+/// A bounded pool of [`SurrealDbClient`] connections sharing one set of
+/// credentials, letting a multithreaded service issue concurrent queries
+/// without serializing them behind a single `Mutex<SurrealDbClient>`.
+pub struct SurrealDbPool {
+    base_url: String,
+    namespace: String,
+    database: String,
+    auth: Auth,
+    compression: bool,
+    clients: Mutex<VecDeque<Box<dyn SimpleHttpClient>>>,
+}
+
+impl SurrealDbPool {
+    /// Build a pool from the shared connection config plus the set of
+    /// `SimpleHttpClient` instances it hands out; the pool's size is
+    /// bounded by how many instances are supplied here.
+    pub fn new(base_url: &str, namespace: &str, database: &str, auth: Auth, clients: Vec<Box<dyn SimpleHttpClient>>)->Arc<Self> {
+        Self::with_compression(base_url, namespace, database, auth, clients, false)
+    }
+
+    /// Like [`SurrealDbPool::new`], but every connection handed out by the
+    /// pool opts into gzip compression, see [`SurrealDbClient::set_compression`].
+    pub fn with_compression(base_url: &str, namespace: &str, database: &str, auth: Auth, clients: Vec<Box<dyn SimpleHttpClient>>, compression: bool)->Arc<Self> {
+        Arc::new(Self {
+            base_url: base_url.to_owned(),
+            namespace: namespace.to_owned(),
+            database: database.to_owned(),
+            auth,
+            compression,
+            clients: Mutex::new(clients.into()),
+        })
+    }
+
+    /// Check out a connection from the pool. Returns `None` if every
+    /// connection is currently checked out.
+    pub fn get(self: &Arc<Self>)->Option<SurrealDbPoolGuard> {
+        let client = self.clients.lock().unwrap().pop_front()?;
+        let checked_out = SurrealDbClient {
+            base_url: self.base_url.clone(),
+            namespace: self.namespace.clone(),
+            database: self.database.clone(),
+            auth: self.auth.clone(),
+            compression: self.compression,
+            client,
+        };
+        Some(SurrealDbPoolGuard { pool: self.clone(), client: Some(checked_out) })
+    }
+}
+
+/// A checked-out [`SurrealDbClient`] that derefs transparently to it and
+/// returns its `SimpleHttpClient` to the owning [`SurrealDbPool`] on drop.
+pub struct SurrealDbPoolGuard {
+    pool: Arc<SurrealDbPool>,
+    client: Option<SurrealDbClient>,
+}
+
+impl Deref for SurrealDbPoolGuard {
+    type Target = SurrealDbClient;
+    fn deref(&self)->&SurrealDbClient {
+        self.client.as_ref().expect("client taken before drop")
+    }
+}
+
+impl DerefMut for SurrealDbPoolGuard {
+    fn deref_mut(&mut self)->&mut SurrealDbClient {
+        self.client.as_mut().expect("client taken before drop")
+    }
+}
+
+impl Drop for SurrealDbPoolGuard {
+    fn drop(&mut self) {
+        if let Some(checked_out) = self.client.take() {
+            self.pool.clients.lock().unwrap().push_back(checked_out.client);
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::{str::from_utf8, env};
 
     use serde::Deserialize;
     use serde_json::Value;
+    use simplehttp::simplehttp::{SimpleHttpClient, SimpleHttpError};
     use simplehttp::simplehttp_reqwest::SimpleHttpClientReqwest;
     use crate::surreal::SurrealStatementReply;
 
-    use super::SurrealDbClient;
+    use super::{Auth, SurrealDbClient, SurrealDbError};
+
+    /// A `SimpleHttpClient` that never actually makes a request, for tests
+    /// that only need a value to satisfy the type and should fail loudly if
+    /// a request is attempted unexpectedly.
+    struct DummyHttpClient;
+
+    impl SimpleHttpClient for DummyHttpClient {
+        fn get(&self, _url: &str, _headers: &[(&str,&str)])->Result<Vec<u8>, SimpleHttpError> {
+            unimplemented!("DummyHttpClient does not make real requests")
+        }
+        fn delete(&self, _url: &str, _headers: &[(&str,&str)])->Result<Vec<u8>, SimpleHttpError> {
+            unimplemented!("DummyHttpClient does not make real requests")
+        }
+        fn post(&self, _url: &str, _headers: &[(&str,&str)], _body: &[u8])->Result<Vec<u8>, SimpleHttpError> {
+            unimplemented!("DummyHttpClient does not make real requests")
+        }
+    }
+
+    #[test]
+    fn auth_header_value_formats_basic_and_bearer() {
+        assert_eq!(Auth::Basic { token: "abc".to_owned() }.header_value(), "Basic abc");
+        assert_eq!(Auth::Bearer { token: "xyz".to_owned() }.header_value(), "Bearer xyz");
+    }
+
+    #[test]
+    fn sign_in_rejects_non_object_credentials_before_calling_out() {
+        let mut surreal = SurrealDbClient::new("root", "root", "http://localhost:8000", "ns", "db", Box::new(DummyHttpClient));
+        let result = surreal.sign_in(None, &Value::String("not an object".to_owned()));
+        assert!(matches!(result, Err(SurrealDbError::InvalidCredentials)));
+    }
+
+    #[test]
+    fn pool_hands_out_connections_until_exhausted_then_refills_on_drop() {
+        let pool = super::SurrealDbPool::new(
+            "http://localhost:8000",
+            "ns",
+            "db",
+            Auth::Basic { token: "root".to_owned() },
+            vec![Box::new(DummyHttpClient), Box::new(DummyHttpClient)],
+        );
+
+        let first = pool.get().expect("pool should hand out its first connection");
+        let second = pool.get().expect("pool should hand out its second connection");
+        assert!(pool.get().is_none(), "pool should be exhausted after handing out both connections");
+
+        drop(first);
+        let third = pool.get().expect("dropping a guard should return its connection to the pool");
+        assert!(pool.get().is_none(), "pool should be exhausted again after handing out the returned connection");
+
+        drop(second);
+        drop(third);
+        assert!(pool.get().is_some(), "both connections should be back in the pool");
+    }
+
+    fn ok_reply()->super::DynamicSurrealStatementReply {
+        super::DynamicSurrealStatementReply { status: super::SurrealStatus::OK, result: Some(vec![]) }
+    }
+
+    fn err_reply()->super::DynamicSurrealStatementReply {
+        super::DynamicSurrealStatementReply { status: super::SurrealStatus::ERR, result: None }
+    }
+
+    #[test]
+    fn align_transaction_replies_drops_begin_and_commit_on_exact_count() {
+        let replies = vec![ok_reply(), ok_reply(), ok_reply(), ok_reply()];
+        let aligned = super::align_transaction_replies(replies, 2).unwrap();
+        assert_eq!(aligned.len(), 2);
+    }
+
+    #[test]
+    fn align_transaction_replies_errors_on_unexpected_count() {
+        let replies = vec![ok_reply(), ok_reply(), ok_reply()];
+        let result = super::align_transaction_replies(replies, 2);
+        assert!(matches!(result, Err(SurrealDbError::TransactionReplyMismatch)));
+    }
+
+    #[test]
+    fn align_transaction_replies_errors_on_statement_err() {
+        let replies = vec![ok_reply(), ok_reply(), err_reply(), ok_reply()];
+        let result = super::align_transaction_replies(replies, 2);
+        assert!(matches!(result, Err(SurrealDbError::NotOkStatus(super::SurrealStatus::ERR))));
+    }
+
+    #[test]
+    fn builder_builds_with_all_required_fields() {
+        let client = SurrealDbClient::builder()
+            .base_url("http://localhost:8000")
+            .namespace("ns")
+            .database("db")
+            .username_password("root", "root")
+            .client(Box::new(DummyHttpClient))
+            .build();
+        assert!(client.is_ok());
+    }
+
+    #[test]
+    fn builder_reports_first_missing_field() {
+        let result = SurrealDbClient::builder()
+            .namespace("ns")
+            .database("db")
+            .username_password("root", "root")
+            .client(Box::new(DummyHttpClient))
+            .build();
+        assert!(matches!(result, Err(SurrealDbError::MissingField("base_url"))));
+    }
+
+    #[test]
+    fn builder_requires_credentials() {
+        let result = SurrealDbClient::builder()
+            .base_url("http://localhost:8000")
+            .namespace("ns")
+            .database("db")
+            .client(Box::new(DummyHttpClient))
+            .build();
+        assert!(matches!(result, Err(SurrealDbError::MissingField("username_password or token"))));
+    }
+
+    #[test]
+    fn bind_params_prepends_a_let_statement_per_param() {
+        let name = Value::String("bob".to_owned());
+        let age = Value::from(42);
+        let params: Vec<(&str, &Value)> = vec![("name", &name), ("age", &age)];
+        let bound = SurrealDbClient::bind_params("SELECT * FROM person WHERE name = $name AND age = $age", &params);
+        assert_eq!(
+            bound,
+            "LET $name = \"bob\";\nLET $age = 42;\nSELECT * FROM person WHERE name = $name AND age = $age"
+        );
+    }
+
+    #[test]
+    fn bind_params_with_no_params_is_just_the_query() {
+        let bound = SurrealDbClient::bind_params("SELECT * FROM person", &[]);
+        assert_eq!(bound, "SELECT * FROM person");
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Film {
+        title: String,
+    }
+
+    #[test]
+    fn link_deserializes_unresolved_id_string() {
+        let link: super::Link<Film> = serde_json::from_str(r#""film:1""#).unwrap();
+        assert!(matches!(link, super::Link::Id(id) if id == "film:1"));
+    }
+
+    #[test]
+    fn link_deserializes_fetched_record() {
+        let link: super::Link<Film> = serde_json::from_str(r#"{"title":"Alien"}"#).unwrap();
+        match link {
+            super::Link::Record(film) => assert_eq!(film, Film { title: "Alien".to_owned() }),
+            super::Link::Id(id) => panic!("expected Link::Record, got Link::Id({id})"),
+        }
+    }
+
+    #[test]
+    fn maybe_decompress_passes_plain_bytes_through_unchanged() {
+        let plain = b"{\"hello\":\"world\"}".to_vec();
+        let result = SurrealDbClient::maybe_decompress(plain.clone()).unwrap();
+        assert_eq!(result, plain);
+    }
+
+    #[test]
+    fn maybe_decompress_inflates_a_gzip_stream() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"{\"hello\":\"world\"}").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let result = SurrealDbClient::maybe_decompress(compressed).unwrap();
+        assert_eq!(result, b"{\"hello\":\"world\"}");
+    }
 
     fn create_test_client()->SurrealDbClient {
         let host = env::var("SURREAL_URL").unwrap_or("http://localhost:8000".to_owned());